@@ -12,7 +12,8 @@
 //! _Note on text:_  This library does not provide any capabilities to map
 //! text/characters to glyph ids. Instead, you should use a proper shaping
 //! library (like [`rustybuzz`]) to do this step. This will take care of proper
-//! glyph positioning, ligatures and more.
+//! glyph positioning, ligatures and more. The resulting advances and offsets
+//! can then be turned into pixel positions with a [`Pen`].
 //!
 //! _Note on emojis:_ This library only supports normal outlines. How to best
 //! render bitmap, SVG and colored glyphs depends very much on your rendering
@@ -24,7 +25,7 @@
 #![deny(missing_docs)]
 
 use std::fmt::{self, Debug, Formatter};
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
 use ttf_parser::{Face, GlyphId, OutlineBuilder, Rect};
 
@@ -57,16 +58,76 @@ impl Glyph {
     /// `ttf-parser`, you can [create a face](ttf_parser::Face::from_slice) from
     /// raw OpenType font bytes with very little overhead.
     ///
-    /// Returns `None` if the glyph does not exist or the outline is malformed.
+    /// Returns `None` if the glyph does not exist or the outline is malformed
+    /// (for example, because it has non-finite coordinates).
     pub fn load(face: &Face, glyph_id: GlyphId) -> Option<Self> {
         let mut builder = Builder::default();
+        let bbox = face.outline_glyph(glyph_id, &mut builder)?;
+        if builder.malformed {
+            return None;
+        }
+
         Some(Self {
             units_per_em: face.units_per_em(),
-            bbox: face.outline_glyph(glyph_id, &mut builder)?,
+            bbox,
+            segments: builder.segments,
+        })
+    }
+
+    /// Build a glyph from a custom outline.
+    ///
+    /// The `outline` function should draw the glyph's contours into the given
+    /// builder in font design units, with the Y-axis pointing up (just like
+    /// `ttf-parser` does when loading a glyph from a face). The bounding box is
+    /// computed from the points of the outline.
+    ///
+    /// Returns `None` if the outline is empty or has non-finite coordinates.
+    pub fn from_outline(
+        units_per_em: u16,
+        outline: impl FnOnce(&mut dyn OutlineBuilder),
+    ) -> Option<Self> {
+        let mut builder = Builder::default();
+        outline(&mut builder);
+        if builder.malformed {
+            return None;
+        }
+
+        Some(Self {
+            units_per_em,
+            bbox: bbox(&builder.segments)?,
             segments: builder.segments,
         })
     }
 
+    /// Stroke the outline of the glyph.
+    ///
+    /// Returns a new glyph that covers everything within `width / 2` of this
+    /// glyph's outline. The `width` is given in font design units, so that the
+    /// stroke scales along with the glyph. The `join` defines how the stroke is
+    /// closed at the corners of the outline.
+    ///
+    /// Curves are flattened before stroking, so the resulting outline consists
+    /// only of lines and the circular arcs of round joins.
+    pub fn stroke(&self, width: f32, join: Join) -> Self {
+        let tolerance = self.units_per_em as f32 * STROKE_TOLERANCE;
+        let mut stroker = Stroker { segments: vec![], hw: 0.5 * width.max(0.0), join };
+
+        for polyline in flatten(&self.segments, tolerance) {
+            stroker.polyline(polyline);
+        }
+
+        Self {
+            units_per_em: self.units_per_em,
+            bbox: bbox(&stroker.segments).unwrap_or(Rect {
+                x_min: 0,
+                y_min: 0,
+                x_max: 0,
+                y_max: 0,
+            }),
+            segments: stroker.segments,
+        }
+    }
+
     /// Rasterize the glyph.
     ///
     /// # Placing & scaling
@@ -96,11 +157,101 @@ impl Glyph {
     /// let's say the returned values are `left: 3`, `top: 1`, `width: 6` and
     /// `height: 9`. Then you need to apply the coverage values to your canvas
     /// starting at `(3, 1)` and going to `(9, 10)` row-by-row.
+    ///
+    /// If the bitmap would be unreasonably large (wider or taller than `8192`
+    /// pixels), the arguments aren't finite or the size is negative, an empty
+    /// bitmap is returned.
     pub fn rasterize(&self, x: f32, y: f32, size: f32) -> Bitmap {
+        let placement = self.place(x, y, size);
+        let canvas = self.draw(x, y, &placement);
+        Bitmap {
+            left: placement.left,
+            top: placement.top,
+            width: placement.width,
+            height: placement.height,
+            coverage: canvas.accumulate(),
+        }
+    }
+
+    /// Rasterize the glyph with its origin snapped to the nearest pixel.
+    ///
+    /// This is the same as [`rasterize`](Self::rasterize), except that `x` and
+    /// `y` are rounded to whole pixels first. Outline edges that lie on whole
+    /// pixels in font units at the given size then also end up on pixel
+    /// boundaries, so that vertical stems look crisper.
+    ///
+    /// Prefer this for UI text at small sizes, where sharpness matters more
+    /// than exact spacing. When rendering runs of text, keep in mind that
+    /// snapping each glyph individually distorts the inter-glyph spacing by up
+    /// to half a pixel, which is what subpixel positioning avoids.
+    pub fn rasterize_snapped(&self, x: f32, y: f32, size: f32) -> Bitmap {
+        self.rasterize(x.round(), y.round(), size)
+    }
+
+    /// Rasterize the glyph, but keep the signed, unclamped coverage.
+    ///
+    /// Placement and scaling work exactly like for [`rasterize`](Self::rasterize).
+    /// The returned values are the accumulated (i.e. prefix-summed) signed
+    /// winding coverage of each pixel, before the absolute value is taken and
+    /// it is clamped to `1.0`. This means that overlapping parts of an outline
+    /// can have values larger than `1.0` and that the sign depends on the
+    /// direction of the outline.
+    ///
+    /// This is useful for compositing multiple glyphs of the same color: You
+    /// can sum up the raw values of all glyphs in a shared raster and only then
+    /// convert them to coverage, for example through
+    /// [`into_bitmap`](RawBitmap::into_bitmap).
+    pub fn rasterize_raw(&self, x: f32, y: f32, size: f32) -> RawBitmap {
+        let placement = self.place(x, y, size);
+        let canvas = self.draw(x, y, &placement);
+        RawBitmap {
+            left: placement.left,
+            top: placement.top,
+            width: placement.width,
+            height: placement.height,
+            values: canvas.accumulate_raw(),
+        }
+    }
+
+    /// Rasterize the glyph row by row.
+    ///
+    /// Placement and scaling work exactly like for [`rasterize`](Self::rasterize)
+    /// and the returned [`Rows`] know the bitmap's `left`, `top`, `width` and
+    /// `height` up front. But instead of producing the whole coverage bitmap at
+    /// once, each row is only rasterized when it is requested. This keeps the
+    /// memory usage at a single row, which is useful for very large glyphs
+    /// that are streamed into some output anyway. The rows match the bitmap
+    /// returned by [`rasterize`](Self::rasterize) up to rounding.
+    ///
+    /// This trades time for memory: Each row draws all segments that intersect
+    /// it anew, so curves spanning many rows are flattened once per row. When
+    /// the whole bitmap fits into memory, [`rasterize`](Self::rasterize) is
+    /// faster.
+    pub fn rasterize_rows(&self, x: f32, y: f32, size: f32) -> Rows<'_> {
+        let placement = self.place(x, y, size);
+        Rows {
+            glyph: self,
+            x,
+            y,
+            canvas: Canvas::new(placement.width, 1),
+            coverage: Vec::with_capacity(placement.width as usize),
+            placement,
+            next: 0,
+        }
+    }
+
+    /// Determine where the glyph lands in the larger pixel raster.
+    fn place(&self, x: f32, y: f32, size: f32) -> Placement {
         // Scale is in pixel per em, but curve data is in font design units, so
         // we have to divide by units per em.
         let s = size / self.units_per_em as f32;
 
+        // Non-finite values would lead to a bogus bounding box, so we produce
+        // an empty bitmap instead.
+        if !x.is_finite() || !y.is_finite() || !s.is_finite() {
+            return Placement::empty(s);
+        }
+
         // Determine the pixel-aligned bounding box of the glyph in the larger
         // pixel raster. For y, we flip and sign and min/max because Y-up. We
         // add a bit of horizontal slack to prevent floating problems when the
@@ -111,36 +262,163 @@ impl Glyph {
         let right = (x + s * self.bbox.x_max as f32 + slack).ceil() as i32;
         let top = (y - s * self.bbox.y_max as f32).floor() as i32;
         let bottom = (y - s * self.bbox.y_min as f32).ceil() as i32;
-        let width = (right - left) as u32;
-        let height = (bottom - top) as u32;
+        let width = right.saturating_sub(left);
+        let height = bottom.saturating_sub(top);
+
+        // Refuse to allocate unreasonably large bitmaps, which can result from
+        // malformed fonts. Negative sizes flip the bounding box, so we don't
+        // draw anything for them either.
+        let valid = 0..=MAX_DIMENSION as i32;
+        if !valid.contains(&width) || !valid.contains(&height) {
+            return Placement::empty(s);
+        }
+
+        Placement {
+            s,
+            left,
+            top,
+            width: width as u32,
+            height: height as u32,
+        }
+    }
 
+    /// Draw the glyph into a fresh canvas spanning the placement.
+    fn draw(&self, x: f32, y: f32, placement: &Placement) -> Canvas {
         // Create function to transform individual points.
-        let dx = x - left as f32;
-        let dy = y - top as f32;
+        let s = placement.s;
+        let dx = x - placement.left as f32;
+        let dy = y - placement.top as f32;
         let t = |p: Point| point(dx + p.x * s, dy - p.y * s);
 
         // Draw!
-        let mut canvas = Canvas::new(width, height);
+        let mut canvas = Canvas::new(placement.width, placement.height);
         for &segment in &self.segments {
-            match segment {
-                Segment::Line(p0, p1) => canvas.line(t(p0), t(p1)),
-                Segment::Quad(p0, p1, p2) => canvas.quad(t(p0), t(p1), t(p2)),
-                Segment::Cubic(p0, p1, p2, p3) => {
-                    canvas.cubic(t(p0), t(p1), t(p2), t(p3))
-                }
+            canvas.segment(segment.map(t));
+        }
+
+        canvas
+    }
+
+    /// Draw only the pixel row `row` of the larger raster into a canvas that
+    /// is one pixel high and spans the placement horizontally.
+    fn draw_row(
+        &self,
+        x: f32,
+        y: f32,
+        placement: &Placement,
+        row: i32,
+        canvas: &mut Canvas,
+    ) {
+        // Create function to transform individual points, such that
+        // the row ends up at the top of the canvas.
+        let s = placement.s;
+        let dx = x - placement.left as f32;
+        let dy = y - row as f32;
+        let t = |p: Point| point(dx + p.x * s, dy - p.y * s);
+
+        // Draw only the segments that can intersect the row. Since a curve
+        // lies within the convex hull of its control points, it suffices to
+        // check those.
+        for &segment in &self.segments {
+            let segment = segment.map(t);
+            let (min, max) = segment.y_extent();
+            if max > 0.0 && min < 1.0 {
+                canvas.segment(segment);
             }
         }
+    }
 
-        Bitmap {
-            left,
-            top,
-            width,
-            height,
-            coverage: canvas.accumulate(),
+    /// Compute the coverage of a single pixel without rasterizing the whole
+    /// glyph.
+    ///
+    /// The glyph is placed and scaled with `x`, `y` and `size` just like for
+    /// [`rasterize`](Self::rasterize). Then, the coverage of the pixel at `(px,
+    /// py)` in the larger raster is returned. Pixels outside of the glyph's
+    /// bitmap have zero coverage.
+    ///
+    /// This only draws the parts of the outline intersecting the pixel's row,
+    /// so it is much cheaper than full rasterization when you only need to
+    /// sample a few pixels. The result matches the bitmap returned by
+    /// [`rasterize`](Self::rasterize) up to rounding.
+    pub fn coverage_at(&self, x: f32, y: f32, size: f32, px: i32, py: i32) -> u8 {
+        let placement = self.place(x, y, size);
+        let column = px - placement.left;
+        if column < 0
+            || column >= placement.width as i32
+            || py < placement.top
+            || py >= placement.top + placement.height as i32
+        {
+            return 0;
+        }
+
+        let mut canvas = Canvas::new(placement.width, 1);
+        self.draw_row(x, y, &placement, py, &mut canvas);
+        canvas.accumulate_at(column as usize)
+    }
+}
+
+impl Segment {
+    /// Transform all points of the segment.
+    fn map(self, f: impl Fn(Point) -> Point) -> Self {
+        match self {
+            Self::Line(p0, p1) => Self::Line(f(p0), f(p1)),
+            Self::Quad(p0, p1, p2) => Self::Quad(f(p0), f(p1), f(p2)),
+            Self::Cubic(p0, p1, p2, p3) => Self::Cubic(f(p0), f(p1), f(p2), f(p3)),
         }
     }
+
+    /// The smallest and largest y coordinate of the segment's points.
+    fn y_extent(self) -> (f32, f32) {
+        let extent = |ys: &[f32]| {
+            ys.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &y| {
+                (min.min(y), max.max(y))
+            })
+        };
+
+        match self {
+            Self::Line(p0, p1) => extent(&[p0.y, p1.y]),
+            Self::Quad(p0, p1, p2) => extent(&[p0.y, p1.y, p2.y]),
+            Self::Cubic(p0, p1, p2, p3) => extent(&[p0.y, p1.y, p2.y, p3.y]),
+        }
+    }
+}
+
+/// How the corners of a stroked outline are joined.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Join {
+    /// Extend the outer edges of the stroke until they meet.
+    ///
+    /// The value is the miter limit: If the distance from the corner to the
+    /// tip of the miter would exceed this multiple of half the stroke width,
+    /// the corner is beveled instead. A common choice is `4.0`.
+    Miter(f32),
+    /// Close the corner with a circular arc.
+    Round,
+    /// Close the corner with a straight line.
+    Bevel,
+}
+
+/// Where a glyph lands in the larger pixel raster.
+struct Placement {
+    /// The scale from font design units to pixels.
+    s: f32,
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Placement {
+    /// A placement without any pixels.
+    fn empty(s: f32) -> Self {
+        Self { s, left: 0, top: 0, width: 0, height: 0 }
+    }
 }
 
+/// The maximum width and height of a glyph's bitmap in pixels. Glyphs that
+/// would be larger than this are rasterized into empty bitmaps.
+const MAX_DIMENSION: usize = 8192;
+
 /// The result of rasterizing a glyph.
 pub struct Bitmap {
     /// Horizontal pixel position (from the left) at which the bitmap should be
@@ -172,32 +450,472 @@ impl Debug for Bitmap {
     }
 }
 
+impl Bitmap {
+    /// Compare the coverage of this bitmap with another one.
+    ///
+    /// Both bitmaps are compared in the larger raster they are placed in, so
+    /// bitmaps with different placement or dimensions can be compared, too.
+    /// Pixels that lie outside of one of the bitmaps count as uncovered in that
+    /// bitmap.
+    ///
+    /// This is useful for checking that a rendering change stays within some
+    /// tolerance instead of requiring bit-exact output.
+    pub fn diff(&self, other: &Bitmap) -> BitmapDiff {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+
+        let mut diff = BitmapDiff::default();
+        for y in top..bottom {
+            for x in left..right {
+                let delta = self.get(x, y).abs_diff(other.get(x, y));
+                if delta > 0 {
+                    diff.max_delta = diff.max_delta.max(delta);
+                    diff.differing += 1;
+                }
+            }
+        }
+
+        diff
+    }
+
+    /// Dilate the coverage by `radius` pixels.
+    ///
+    /// Each pixel takes on the largest coverage within a square of `radius`
+    /// pixels around it, so that the glyph grows by `radius` in every
+    /// direction. For fractional radii, the result is interpolated between the
+    /// neighbouring whole-pixel radii. The bitmap is expanded by `radius`
+    /// (rounded up) on every side to make room for the grown glyph and `left`
+    /// and `top` are adjusted accordingly. If the expanded bitmap would be
+    /// larger than a rasterized glyph may be (wider or taller than `8192`
+    /// pixels), the bitmap is left unchanged.
+    ///
+    /// This is useful for darkening thin stems on low-resolution displays.
+    pub fn dilate(&mut self, radius: f32) {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return;
+        }
+
+        let pad = radius.ceil() as usize;
+        let grow = |len: u32| pad.checked_mul(2)?.checked_add(len as usize);
+        let (Some(width), Some(height)) = (grow(self.width), grow(self.height)) else {
+            return;
+        };
+
+        if width.max(height) > MAX_DIMENSION {
+            return;
+        }
+
+        self.pad(pad, width, height);
+        self.morph(radius, 0);
+    }
+
+    /// Erode the coverage by `radius` pixels.
+    ///
+    /// This is the counterpart to [`dilate`](Self::dilate): Each pixel takes
+    /// on the smallest coverage within the square around it, so that the glyph
+    /// shrinks. Pixels outside of the bitmap count as uncovered. The placement
+    /// and dimensions of the bitmap stay the same.
+    ///
+    /// Eroding a dilated bitmap by the same whole-pixel radius restores the
+    /// original coverage, except for gaps narrower than the square, which stay
+    /// filled. For fractional radii, this round trip is only approximate.
+    pub fn erode(&mut self, radius: f32) {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return;
+        }
+
+        // Every window this large reaches beyond the bitmap.
+        if radius >= self.width.max(self.height) as f32 {
+            self.coverage.fill(0);
+            return;
+        }
+
+        // Eroding the coverage is the same as dilating the inverse coverage.
+        self.coverage.iter_mut().for_each(|c| *c = 255 - *c);
+        self.morph(radius, 255);
+        self.coverage.iter_mut().for_each(|c| *c = 255 - *c);
+    }
+
+    /// Expand the bitmap by `pad` uncovered pixels on every side, resulting in
+    /// the given new dimensions.
+    fn pad(&mut self, pad: usize, width: usize, height: usize) {
+        let mut coverage = vec![0; width * height];
+        for (y, row) in self.coverage.chunks_exact(self.width.max(1) as usize).enumerate()
+        {
+            let start = (y + pad) * width + pad;
+            coverage[start..start + row.len()].copy_from_slice(row);
+        }
+
+        self.left = self.left.saturating_sub(pad as i32);
+        self.top = self.top.saturating_sub(pad as i32);
+        self.width = width as u32;
+        self.height = height as u32;
+        self.coverage = coverage;
+    }
+
+    /// Replace each coverage value with the largest one within `radius`,
+    /// treating pixels outside of the bitmap as having coverage `outside`.
+    ///
+    /// The square window is separable, so we first filter horizontally and
+    /// then vertically.
+    fn morph(&mut self, radius: f32, outside: u8) {
+        let (w, h) = (self.width as usize, self.height as usize);
+
+        // Beyond the bitmap's extent, a larger window doesn't change anything.
+        let radius = radius.min(w.max(h) as f32 + 1.0);
+
+        for row in self.coverage.chunks_exact_mut(w.max(1)) {
+            let max = window_max(row, radius, outside);
+            row.copy_from_slice(&max);
+        }
+
+        let mut column = Vec::with_capacity(h);
+        for x in 0..w {
+            column.clear();
+            column.extend(self.coverage.iter().skip(x).step_by(w).copied());
+            let max = window_max(&column, radius, outside);
+            for (c, m) in self.coverage.iter_mut().skip(x).step_by(w).zip(max) {
+                *c = m;
+            }
+        }
+    }
+
+    /// The coverage at a pixel position in the larger raster.
+    fn get(&self, x: i32, y: i32) -> u8 {
+        if x < self.left || x >= self.right() || y < self.top || y >= self.bottom() {
+            return 0;
+        }
+
+        let i = (y - self.top) as usize * self.width as usize + (x - self.left) as usize;
+        self.coverage.get(i).copied().unwrap_or(0)
+    }
+
+    /// The exclusive right edge of the bitmap in the larger raster.
+    fn right(&self) -> i32 {
+        self.left + self.width as i32
+    }
+
+    /// The exclusive bottom edge of the bitmap in the larger raster.
+    fn bottom(&self) -> i32 {
+        self.top + self.height as i32
+    }
+}
+
+/// The largest value within `radius` of each position in a line, where
+/// values beyond the ends of the line count as `outside`.
+///
+/// For fractional radii, this interpolates between the largest values for the
+/// neighbouring whole radii.
+fn window_max(line: &[u8], radius: f32, outside: u8) -> Vec<u8> {
+    let n = radius.floor() as usize;
+    let frac = radius - n as f32;
+    let max = running_max(line, n, outside);
+    if frac > 0.0 {
+        let edge = running_max(line, n + 1, outside);
+        max.iter()
+            .zip(edge)
+            .map(|(&max, edge)| (max as f32 + frac * (edge - max) as f32).round() as u8)
+            .collect()
+    } else {
+        max
+    }
+}
+
+/// The largest value within the whole-pixel `radius` of each position in a
+/// line, where values beyond the ends of the line count as `outside`.
+///
+/// This is the van Herk/Gil-Werman algorithm: The padded line is split into
+/// blocks as long as the window and every window is covered by the suffix of
+/// one block and the prefix of the next. Thus, the cost doesn't depend on the
+/// radius.
+fn running_max(line: &[u8], radius: usize, outside: u8) -> Vec<u8> {
+    // A window reaching beyond both ends of the line doesn't grow any further.
+    let n = radius.min(line.len());
+    let k = 2 * n + 1;
+
+    let mut padded = vec![outside; n];
+    padded.extend_from_slice(line);
+    padded.resize(line.len() + 2 * n, outside);
+
+    let mut prefix = padded.clone();
+    let mut suffix = padded;
+    for i in 1..prefix.len() {
+        if i % k != 0 {
+            prefix[i] = prefix[i].max(prefix[i - 1]);
+        }
+    }
+
+    for i in (1..suffix.len()).rev() {
+        if i % k != 0 {
+            suffix[i - 1] = suffix[i - 1].max(suffix[i]);
+        }
+    }
+
+    (0..line.len()).map(|i| suffix[i].max(prefix[i + k - 1])).collect()
+}
+
+/// Rasterizes a glyph row by row.
+///
+/// Created by [`Glyph::rasterize_rows`]. Use [`next_row`](Self::next_row) to
+/// step through the rows. This is not an [`Iterator`] because each row borrows
+/// from a buffer that is reused for the next one.
+pub struct Rows<'a> {
+    glyph: &'a Glyph,
+    x: f32,
+    y: f32,
+    placement: Placement,
+    canvas: Canvas,
+    coverage: Vec<u8>,
+    next: u32,
+}
+
+impl Rows<'_> {
+    /// Horizontal pixel position (from the left) at which the rows should be
+    /// placed in the larger raster.
+    pub fn left(&self) -> i32 {
+        self.placement.left
+    }
+
+    /// Vertical pixel position (from the top) at which the first row should be
+    /// placed in the larger raster.
+    pub fn top(&self) -> i32 {
+        self.placement.top
+    }
+
+    /// The width of each row in pixels.
+    pub fn width(&self) -> u32 {
+        self.placement.width
+    }
+
+    /// The number of rows.
+    pub fn height(&self) -> u32 {
+        self.placement.height
+    }
+
+    /// Rasterize the next row.
+    ///
+    /// Returns the index of the row (counting from `top`) along with its
+    /// coverage values, or `None` once all rows have been produced. The
+    /// coverage values have the same meaning as in [`Bitmap::coverage`].
+    pub fn next_row(&mut self) -> Option<(u32, &[u8])> {
+        if self.next >= self.placement.height {
+            return None;
+        }
+
+        let index = self.next;
+        let row = self.placement.top + index as i32;
+        self.glyph
+            .draw_row(self.x, self.y, &self.placement, row, &mut self.canvas);
+        self.canvas.accumulate_into(&mut self.coverage);
+        self.next += 1;
+
+        Some((index, &self.coverage))
+    }
+}
+
+impl Debug for Rows<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Rows")
+            .field("left", &self.left())
+            .field("top", &self.top())
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish()
+    }
+}
+
+/// The result of rasterizing a glyph without clamping the coverage.
+///
+/// See [`Glyph::rasterize_raw`] for more details.
+pub struct RawBitmap {
+    /// Horizontal pixel position (from the left) at which the bitmap should be
+    /// placed in the larger raster.
+    pub left: i32,
+    /// Vertical pixel position (from the top) at which the bitmap should be
+    /// placed in the larger raster.
+    pub top: i32,
+    /// The width of the bitmap in pixels.
+    pub width: u32,
+    /// The height of the bitmap in pixels.
+    pub height: u32,
+    /// The accumulated signed coverage of each pixel. A magnitude of `1.0` or
+    /// more means 100% coverage.
+    ///
+    /// The length of this vector is `width * height`, with the values being
+    /// stored row-by-row.
+    pub values: Vec<f32>,
+}
+
+impl RawBitmap {
+    /// Clamp the signed values into a coverage bitmap.
+    ///
+    /// For a raw bitmap of a single glyph, this yields the same result as
+    /// [`Glyph::rasterize`].
+    pub fn into_bitmap(self) -> Bitmap {
+        Bitmap {
+            left: self.left,
+            top: self.top,
+            width: self.width,
+            height: self.height,
+            coverage: self.values.into_iter().map(coverage).collect(),
+        }
+    }
+}
+
+impl Debug for RawBitmap {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("RawBitmap")
+            .field("left", &self.left)
+            .field("top", &self.top)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+/// How much two bitmaps differ, as determined by [`Bitmap::diff`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct BitmapDiff {
+    /// The largest difference in coverage of any single pixel.
+    pub max_delta: u8,
+    /// The number of pixels whose coverage differs.
+    pub differing: usize,
+}
+
+/// Compute a bounding box that contains all points of the segments.
+///
+/// Returns `None` if there are no segments.
+fn bbox(segments: &[Segment]) -> Option<Rect> {
+    let mut min = point(f32::INFINITY, f32::INFINITY);
+    let mut max = point(f32::NEG_INFINITY, f32::NEG_INFINITY);
+    let mut extend = |p: Point| {
+        min = point(min.x.min(p.x), min.y.min(p.y));
+        max = point(max.x.max(p.x), max.y.max(p.y));
+    };
+
+    for &segment in segments {
+        match segment {
+            Segment::Line(p0, p1) => [p0, p1].into_iter().for_each(&mut extend),
+            Segment::Quad(p0, p1, p2) => [p0, p1, p2].into_iter().for_each(&mut extend),
+            Segment::Cubic(p0, p1, p2, p3) => {
+                [p0, p1, p2, p3].into_iter().for_each(&mut extend)
+            }
+        }
+    }
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    let unit = |v: f32| v.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    Some(Rect {
+        x_min: unit(min.x.floor()),
+        y_min: unit(min.y.floor()),
+        x_max: unit(max.x.ceil()),
+        y_max: unit(max.y.ceil()),
+    })
+}
+
+/// Tracks the pen position while rendering a run of shaped glyphs.
+///
+/// Shaping libraries like [`rustybuzz`] report the advances and offsets of the
+/// glyphs in font design units, with the Y-axis pointing up. The pen
+/// accumulates the advances in design units and converts them into the pixel
+/// positions that [`Glyph::rasterize`] expects.
+///
+/// [`rustybuzz`]: https://github.com/RazrFalcon/rustybuzz
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pen {
+    /// The pixel position of the pen origin.
+    origin: (f32, f32),
+    /// How many pixels correspond to `1em`.
+    size: f32,
+    /// The number of font design units per em unit.
+    units_per_em: u16,
+    /// The accumulated advance in font design units.
+    advance: (i64, i64),
+}
+
+impl Pen {
+    /// Create a new pen whose first glyph will be placed at the pixel position
+    /// `(x, y)` when rendered with the given `size` (see
+    /// [`Glyph::rasterize`]).
+    ///
+    /// The `units_per_em` should be the same as for the face the glyphs
+    /// are loaded from.
+    pub fn new(x: f32, y: f32, size: f32, units_per_em: u16) -> Self {
+        Self {
+            origin: (x, y),
+            size,
+            units_per_em,
+            advance: (0, 0),
+        }
+    }
+
+    /// The pixel position at which to rasterize the current glyph, given its
+    /// offset in font design units.
+    ///
+    /// The returned values can be passed directly as `x` and `y` to
+    /// [`Glyph::rasterize`].
+    pub fn position(&self, x_offset: i32, y_offset: i32) -> (f32, f32) {
+        let x = self.advance.0 + i64::from(x_offset);
+        let y = self.advance.1 + i64::from(y_offset);
+        (self.origin.0 + self.scale(x), self.origin.1 - self.scale(y))
+    }
+
+    /// Move the pen by the advance of the current glyph in font design units.
+    pub fn advance(&mut self, x_advance: i32, y_advance: i32) {
+        self.advance.0 += i64::from(x_advance);
+        self.advance.1 += i64::from(y_advance);
+    }
+
+    /// Convert a distance from font design units into pixels.
+    fn scale(&self, units: i64) -> f32 {
+        units as f32 * self.size / self.units_per_em as f32
+    }
+}
+
 /// Builds the glyph outline.
 #[derive(Default)]
 struct Builder {
     segments: Vec<Segment>,
     start: Option<Point>,
     last: Point,
+    /// Whether any of the coordinates was not finite.
+    malformed: bool,
+}
+
+impl Builder {
+    /// Record whether all coordinates are finite.
+    fn check(&mut self, coords: &[f32]) {
+        self.malformed |= !coords.iter().all(|c| c.is_finite());
+    }
 }
 
 impl OutlineBuilder for Builder {
     fn move_to(&mut self, x: f32, y: f32) {
+        self.check(&[x, y]);
         self.start = Some(point(x, y));
         self.last = point(x, y);
     }
 
     fn line_to(&mut self, x: f32, y: f32) {
+        self.check(&[x, y]);
         self.segments.push(Segment::Line(self.last, point(x, y)));
         self.last = point(x, y);
     }
 
     fn quad_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.check(&[x1, y1, x2, y2]);
         self.segments
             .push(Segment::Quad(self.last, point(x1, y1), point(x2, y2)));
         self.last = point(x2, y2);
     }
 
     fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        self.check(&[x1, y1, x2, y2, x3, y3]);
         self.segments.push(Segment::Cubic(
             self.last,
             point(x1, y1),
@@ -215,6 +933,192 @@ impl OutlineBuilder for Builder {
     }
 }
 
+/// The flattening tolerance for stroking, relative to the units per em.
+const STROKE_TOLERANCE: f32 = 1.0 / 2048.0;
+
+/// The maximum number of lines a single curve is flattened into for stroking.
+const MAX_STROKE_SUBDIVISIONS: f32 = 64.0;
+
+/// Flatten the segments into polylines, one for each connected run of
+/// segments.
+///
+/// The number of lines per curve is determined with Wang's formula such that
+/// the lines deviate from the curve by at most `tol`.
+fn flatten(segments: &[Segment], tol: f32) -> Vec<Vec<Point>> {
+    let subdivisions =
+        |dev: f32| (dev / tol).sqrt().ceil().clamp(1.0, MAX_STROKE_SUBDIVISIONS) as usize;
+
+    let mut polylines: Vec<Vec<Point>> = vec![];
+    for &segment in segments {
+        let start = match segment {
+            Segment::Line(p0, ..) | Segment::Quad(p0, ..) | Segment::Cubic(p0, ..) => p0,
+        };
+
+        if polylines.last().and_then(|polyline| polyline.last()) != Some(&start) {
+            polylines.push(vec![start]);
+        }
+
+        let polyline = polylines.last_mut().unwrap();
+        match segment {
+            Segment::Line(_, p1) => polyline.push(p1),
+            Segment::Quad(p0, p1, p2) => {
+                let n = subdivisions(0.25 * hypot2(p0 - 2.0 * p1 + p2).sqrt());
+                for i in 1..n {
+                    let t = i as f32 / n as f32;
+                    polyline.push(lerp(t, lerp(t, p0, p1), lerp(t, p1, p2)));
+                }
+
+                // Evaluating the curve at its end is not always exact, but
+                // the next segment must start precisely here to be joined.
+                polyline.push(p2);
+            }
+            Segment::Cubic(p0, p1, p2, p3) => {
+                let dd = hypot2(p0 - 2.0 * p1 + p2).max(hypot2(p1 - 2.0 * p2 + p3));
+                let n = subdivisions(0.75 * dd.sqrt());
+                for i in 1..n {
+                    let t = i as f32 / n as f32;
+                    let p012 = lerp(t, lerp(t, p0, p1), lerp(t, p1, p2));
+                    let p123 = lerp(t, lerp(t, p1, p2), lerp(t, p2, p3));
+                    polyline.push(lerp(t, p012, p123));
+                }
+                polyline.push(p3);
+            }
+        }
+    }
+
+    polylines
+}
+
+/// Builds the outline of a stroke.
+///
+/// The stroke is composed of one rectangle for each line and one wedge for
+/// each corner. All of these pieces are emitted with the same (clockwise)
+/// orientation, so that their coverage adds up instead of cancelling out
+/// where they overlap.
+struct Stroker {
+    segments: Vec<Segment>,
+    /// Half the stroke width.
+    hw: f32,
+    join: Join,
+}
+
+impl Stroker {
+    /// Stroke a polyline, which is closed if its first and last point match.
+    fn polyline(&mut self, mut points: Vec<Point>) {
+        points.dedup();
+        let closed = points.len() > 2 && points.first() == points.last();
+        if closed {
+            points.pop();
+        }
+
+        let n = points.len();
+        if n < 2 {
+            return;
+        }
+
+        let lines = if closed { n } else { n - 1 };
+        for i in 0..lines {
+            self.rect(points[i], points[(i + 1) % n]);
+        }
+
+        let corners = if closed { 0..n } else { 1..n - 1 };
+        for i in corners {
+            self.corner(points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+        }
+    }
+
+    /// Add the rectangle that strokes the line from `a` to `b`.
+    fn rect(&mut self, a: Point, b: Point) {
+        let n = self.hw * normal(b - a);
+        self.polygon(&[a + n, b + n, b - n, a - n]);
+    }
+
+    /// Add the wedge that closes the corner at `v` between the line from
+    /// `prev` to `v` and the line from `v` to `next`.
+    fn corner(&mut self, prev: Point, v: Point, next: Point) {
+        let d0 = unit(v - prev);
+        let d1 = unit(next - v);
+        let cross = d0.x * d1.y - d0.y * d1.x;
+        let dot = d0.x * d1.x + d0.y * d1.y;
+        if cross.abs() < 1e-6 && dot > 0.0 {
+            return;
+        }
+
+        // The offsets at which the wedge starts and ends on the outer side of
+        // the corner, ordered such that the wedge runs clockwise from `oa` to
+        // `ob`.
+        let n0 = self.hw * normal(d0);
+        let n1 = self.hw * normal(d1);
+        let (oa, ob) = if cross < 0.0 { (n0, n1) } else { (-n1, -n0) };
+
+        // The angle by which the outline turns at the corner.
+        let theta = cross.abs().atan2(dot);
+
+        match self.join {
+            Join::Miter(limit) if (0.5 * theta).cos() * limit >= 1.0 => {
+                let ratio = (0.5 * theta).cos().recip();
+                let tip = v + (self.hw * ratio) * unit(oa + ob);
+                self.polygon(&[v, v + oa, tip, v + ob]);
+            }
+            Join::Miter(_) | Join::Bevel => self.polygon(&[v, v + oa, v + ob]),
+            Join::Round => {
+                // Approximate the arc with quadratic curves spanning at most
+                // 45 degrees each.
+                let k = (theta / std::f32::consts::FRAC_PI_4).ceil().max(1.0);
+                let phi = theta / k;
+                let reach = self.hw / (0.5 * phi).cos();
+                let start = oa.y.atan2(oa.x);
+                let at = |radius: f32, angle: f32| {
+                    v + point(radius * angle.cos(), radius * angle.sin())
+                };
+
+                self.segments.push(Segment::Line(v, v + oa));
+                let mut p = v + oa;
+                for i in 1..=k as usize {
+                    let angle = start - i as f32 * phi;
+                    let end = if i == k as usize { v + ob } else { at(self.hw, angle) };
+                    let control = at(reach, angle + 0.5 * phi);
+                    self.segments.push(Segment::Quad(p, control, end));
+                    p = end;
+                }
+                self.segments.push(Segment::Line(p, v));
+            }
+        }
+    }
+
+    /// Add a closed polygon.
+    fn polygon(&mut self, points: &[Point]) {
+        for (i, &p) in points.iter().enumerate() {
+            self.segments.push(Segment::Line(p, points[(i + 1) % points.len()]));
+        }
+    }
+}
+
+/// The vector of unit length pointing in the same direction.
+fn unit(p: Point) -> Point {
+    p / hypot2(p).sqrt()
+}
+
+/// The unit vector rotated 90 degrees counter-clockwise from the direction.
+fn normal(p: Point) -> Point {
+    let u = unit(p);
+    point(-u.y, u.x)
+}
+
+/// The maximum number of lines a quadratic curve is flattened into for
+/// rasterization.
+const MAX_QUAD_SUBDIVISIONS: f32 = 30.0;
+
+/// The maximum number of quadratic curves a cubic curve is converted into for
+/// rasterization.
+const MAX_CUBIC_SUBDIVISIONS: f32 = 20.0;
+
+/// The largest coordinate magnitude (in pixels) that is drawn as is. Beyond
+/// this, `f32` can't represent fractional pixels anymore, so points are clamped
+/// into this range. This keeps the work per line bounded even for pathological
+/// control points.
+const MAX_COORDINATE: f32 = 16777216.0;
+
 // Accumulation, line and quad drawing taken from here:
 // https://github.com/raphlinus/font-rs
 //
@@ -245,7 +1149,7 @@ impl Canvas {
         Self {
             w: w as usize,
             h: h as usize,
-            a: vec![0.0; (w * h + 4) as usize],
+            a: vec![0.0; w as usize * h as usize + 4],
         }
     }
 
@@ -256,23 +1160,67 @@ impl Canvas {
             .iter()
             .map(|c| {
                 acc += c;
-                (255.0 * acc.abs().min(1.0)) as u8
+                coverage(acc)
             })
             .collect()
     }
 
+    /// Return the accumulated signed values without clamping them.
+    fn accumulate_raw(mut self) -> Vec<f32> {
+        self.a.truncate(self.w * self.h);
+        let mut acc = 0.0;
+        for c in &mut self.a {
+            acc += *c;
+            *c = acc;
+        }
+        self.a
+    }
+
+    /// Write the accumulated coverage values into `out` and clear the canvas
+    /// for reuse.
+    fn accumulate_into(&mut self, out: &mut Vec<u8>) {
+        let mut acc = 0.0;
+        out.clear();
+        out.extend(self.a[..self.w * self.h].iter().map(|c| {
+            acc += c;
+            coverage(acc)
+        }));
+        self.a.fill(0.0);
+    }
+
+    /// Return the accumulated coverage value at an index.
+    fn accumulate_at(&self, i: usize) -> u8 {
+        coverage(self.a[..=i].iter().sum())
+    }
+
+    /// Draw a segment.
+    fn segment(&mut self, segment: Segment) {
+        match segment {
+            Segment::Line(p0, p1) => self.line(p0, p1),
+            Segment::Quad(p0, p1, p2) => self.quad(p0, p1, p2),
+            Segment::Cubic(p0, p1, p2, p3) => self.cubic(p0, p1, p2, p3),
+        }
+    }
+
     /// Add to a value in the accumulation buffer.
+    ///
+    /// Values left of the canvas are added to its first column and values
+    /// right of it to the column just past its end. This keeps the
+    /// accumulated coverage correct for lines that leave the canvas.
     fn add(&mut self, linestart: usize, x: i32, delta: f32) {
-        if let Ok(x) = usize::try_from(x) {
-            if let Some(a) = self.a.get_mut(linestart + x) {
-                *a += delta;
-            }
+        let x = x.clamp(0, self.w as i32) as usize;
+        if let Some(a) = self.a.get_mut(linestart + x) {
+            *a += delta;
         }
     }
 
     /// Draw a straight line.
     fn line(&mut self, p0: Point, p1: Point) {
-        if (p0.y - p1.y).abs() <= core::f32::EPSILON {
+        if !p0.is_finite() || !p1.is_finite() {
+            return;
+        }
+        let (p0, p1) = (p0.clamp(MAX_COORDINATE), p1.clamp(MAX_COORDINATE));
+        if (p0.y - p1.y).abs() <= f32::EPSILON {
             return;
         }
         let (dir, p0, p1) = if p0.y < p1.y { (1.0, p0, p1) } else { (-1.0, p1, p0) };
@@ -308,9 +1256,23 @@ impl Canvas {
                 } else {
                     let a1 = s * (1.5 - x0f);
                     self.add(linestart, x0i + 1, d * (a1 - a0));
-                    for xi in x0i + 2..x1i - 1 {
+                    // Only visit cells within the canvas, so that lines far
+                    // outside of it can't stall the loop. The skipped cells'
+                    // mass is added at the edges instead, so that the row's
+                    // total stays the same.
+                    let (start, end) = (x0i + 2, x1i - 1);
+                    let w = self.w as i32;
+                    for xi in start.max(0)..end.min(w) {
                         self.add(linestart, xi, d * s);
                     }
+                    let before = end.min(0) - start;
+                    if before > 0 {
+                        self.add(linestart, 0, d * s * before as f32);
+                    }
+                    let after = end - start.max(w);
+                    if after > 0 {
+                        self.add(linestart, w, d * s * after as f32);
+                    }
                     let a2 = a1 + (x1i - x0i - 3) as f32 * s;
                     self.add(linestart, x1i - 1, d * (1.0 - a2 - am));
                 }
@@ -333,7 +1295,7 @@ impl Canvas {
 
         // Estimate the required number of subdivisions for flattening.
         let tol = 3.0;
-        let n = 1.0 + (tol * devsq).sqrt().sqrt().floor().min(30.0);
+        let n = 1.0 + (tol * devsq).sqrt().sqrt().floor().min(MAX_QUAD_SUBDIVISIONS);
         let nu = n as usize;
         let step = n.recip();
 
@@ -387,7 +1349,7 @@ impl Canvas {
         // Estimate the required number of subdivisions for conversion.
         let tol = 0.333;
         let max = 432.0 * tol * tol;
-        let n = (err / max).powf(1.0 / 6.0).ceil().clamp(1.0, 20.0);
+        let n = (err / max).powf(1.0 / 6.0).ceil().clamp(1.0, MAX_CUBIC_SUBDIVISIONS);
         let nu = n as usize;
         let step = n.recip();
         let step4 = step / 4.0;
@@ -429,6 +1391,11 @@ impl Canvas {
     }
 }
 
+/// Convert an accumulated signed value into a coverage value.
+fn coverage(acc: f32) -> u8 {
+    (255.0 * acc.abs().min(1.0)) as u8
+}
+
 /// Create a point.
 fn point(x: f32, y: f32) -> Point {
     Point { x, y }
@@ -448,12 +1415,24 @@ fn hypot2(p: Point) -> f32 {
 }
 
 /// A point in 2D.
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 struct Point {
     x: f32,
     y: f32,
 }
 
+impl Point {
+    /// Whether both coordinates are finite.
+    fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+
+    /// Clamp both coordinates into `-max..=max`.
+    fn clamp(self, max: f32) -> Self {
+        point(self.x.clamp(-max, max), self.y.clamp(-max, max))
+    }
+}
+
 impl Add for Point {
     type Output = Self;
 
@@ -470,6 +1449,14 @@ impl Sub for Point {
     }
 }
 
+impl Neg for Point {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self { x: -self.x, y: -self.y }
+    }
+}
+
 impl Mul<Point> for f32 {
     type Output = Point;
 
@@ -1,13 +1,17 @@
 use std::io::Write;
+use std::time::{Duration, Instant};
 
-use pixglyph::Glyph;
-use ttf_parser::{Face, GlyphId};
+use pixglyph::{Bitmap, Glyph, Join, Pen};
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
 
 const ROBOTO: &[u8] = include_bytes!("../fonts/Roboto-Regular.ttf");
 const SOURCE_SANS: &[u8] = include_bytes!("../fonts/SourceSans3-Regular.otf");
 const IBM_PLEX: &[u8] = include_bytes!("../fonts/IBMPlexSans-Bold.ttf");
 const LIBERTINUS: &[u8] = include_bytes!("../fonts/LibertinusSerif-Regular.otf");
 
+/// How much coverage may deviate from the reference images.
+const TOLERANCE: u8 = 2;
+
 #[test]
 fn test_load_all() {
     let face = Face::parse(SOURCE_SANS, 0).unwrap();
@@ -28,14 +32,321 @@ fn test_rasterize() {
     }
 }
 
+#[test]
+fn test_diff() {
+    let a = Bitmap {
+        left: 0,
+        top: 0,
+        width: 2,
+        height: 1,
+        coverage: vec![255, 100],
+    };
+    let b = Bitmap {
+        left: 1,
+        top: 0,
+        width: 2,
+        height: 1,
+        coverage: vec![90, 10],
+    };
+    let diff = a.diff(&b);
+    assert_eq!(diff.max_delta, 255);
+    assert_eq!(diff.differing, 3);
+    assert_eq!(a.diff(&a).differing, 0);
+}
+
+#[test]
+fn test_rasterize_raw() {
+    let glyph = glyph(ROBOTO, 'g');
+    let bitmap = glyph.rasterize(3.2, 7.9, 40.0);
+    let raw = glyph.rasterize_raw(3.2, 7.9, 40.0);
+    assert_eq!(raw.values.len(), (raw.width * raw.height) as usize);
+    assert!(raw.values.iter().any(|&v| v.abs() > 0.5));
+    assert_eq!(raw.into_bitmap().coverage, bitmap.coverage);
+}
+
+#[test]
+fn test_stroke_round_corners() {
+    // At a size of 100px, the stroke is 20px wide and the rectangle's corners
+    // are at (20, 100), (80, 100), (20, 40) and (80, 40).
+    let bitmap = rect(200.0, 0.0, 800.0, 600.0)
+        .stroke(200.0, Join::Round)
+        .rasterize(0.0, 100.0, 100.0);
+    let at = |x, y| pixel(&bitmap, x, y);
+
+    // Pixels diagonally outside of each corner are fully covered (up to
+    // floating point error).
+    assert!(at(15, 105) >= 254);
+    assert!(at(84, 105) >= 254);
+    assert!(at(15, 34) >= 254);
+    assert!(at(84, 34) >= 254);
+
+    // The edges are covered, but the inside is not.
+    assert!(at(50, 100) >= 254);
+    assert!(at(20, 70) >= 254);
+    assert_eq!(at(50, 70), 0);
+}
+
+#[test]
+fn test_stroke_curve_corners() {
+    // Almost the same square as above, but with fractional coordinates and a
+    // slightly curved bottom edge meeting the corners.
+    let square = Glyph::from_outline(1000, |builder| {
+        builder.move_to(200.02, 0.017);
+        builder.quad_to(500.54, -40.02, 800.017, 0.54);
+        builder.line_to(800.017, 600.02);
+        builder.line_to(200.02, 600.02);
+        builder.close();
+    })
+    .unwrap();
+
+    // The corners at both ends of the curve are joined.
+    let bitmap = square.stroke(200.0, Join::Round).rasterize(0.0, 100.0, 100.0);
+    assert!(pixel(&bitmap, 15, 105) >= 254);
+    assert!(pixel(&bitmap, 84, 105) >= 254);
+}
+
+#[test]
+fn test_stroke_miter_corners() {
+    // Same setup as above. The tip of the miter at the bottom left corner is
+    // at (10, 110).
+    let stroked = |join| {
+        rect(200.0, 0.0, 800.0, 600.0)
+            .stroke(200.0, join)
+            .rasterize(0.0, 100.0, 100.0)
+    };
+
+    // The square corner's ratio of sqrt(2) is within the limit, so the pixel
+    // close to the tip is covered.
+    assert!(pixel(&stroked(Join::Miter(4.0)), 11, 108) >= 254);
+
+    // Beveled and rounded corners don't reach there.
+    assert_eq!(pixel(&stroked(Join::Bevel), 11, 108), 0);
+    assert_eq!(pixel(&stroked(Join::Round), 11, 108), 0);
+
+    // With a smaller limit, the miter falls back to a bevel.
+    assert_eq!(pixel(&stroked(Join::Miter(1.2)), 11, 108), 0);
+
+    // All joins cover the corner right next to the vertex.
+    for join in [Join::Miter(4.0), Join::Miter(1.2), Join::Bevel, Join::Round] {
+        assert!(pixel(&stroked(join), 17, 102) >= 254);
+    }
+}
+
+#[test]
+fn test_pen() {
+    // Advances and offsets as a shaper would report them, in font units.
+    let run = [(500, 0, 0, 0), (600, 0, 0, -100), (250, 50, 20, 0)];
+    let mut pen = Pen::new(10.0, 50.0, 20.0, 1000);
+    let mut positions = vec![];
+    for (x_advance, y_advance, x_offset, y_offset) in run {
+        positions.push(pen.position(x_offset, y_offset));
+        pen.advance(x_advance, y_advance);
+    }
+
+    assert_eq!(positions, [(10.0, 50.0), (20.0, 52.0), (32.4, 50.0)]);
+    assert_eq!(pen.position(0, 0), (37.0, 49.0));
+}
+
+#[test]
+fn test_extreme_control_points() {
+    let glyph = Glyph::from_outline(1000, |builder| {
+        builder.move_to(0.0, 0.0);
+        builder.quad_to(1e10, 250.0, 500.0, 500.0);
+        builder.curve_to(-1e30, 400.0, 1e30, 100.0, 0.0, 500.0);
+        builder.close();
+
+        // A regular square below the pathological curves.
+        builder.move_to(0.0, -1000.0);
+        builder.line_to(500.0, -1000.0);
+        builder.line_to(500.0, -500.0);
+        builder.line_to(0.0, -500.0);
+        builder.close();
+    })
+    .unwrap();
+
+    let start = Instant::now();
+    let bitmap = glyph.rasterize(0.0, 0.0, 10.0);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(bitmap.coverage.len(), (bitmap.width * bitmap.height) as usize);
+
+    // The curves don't leak coverage into the rows of the square, which spans
+    // from (0, 5) to (5, 10) in pixels.
+    for y in 5..10 {
+        for x in 0..5 {
+            assert!(pixel(&bitmap, x, y) >= 254);
+        }
+        for x in (-300..0).chain(5..300) {
+            assert_eq!(pixel(&bitmap, x, y), 0);
+        }
+    }
+}
+
+#[test]
+fn test_non_finite() {
+    let outline = |builder: &mut dyn OutlineBuilder, coords: [f32; 8]| {
+        let [x0, y0, x1, y1, x2, y2, x3, y3] = coords;
+        builder.move_to(x0, y0);
+        builder.line_to(x1, y1);
+        builder.quad_to(x2, y2, x3, y3);
+        builder.curve_to(x0, y3, x1, y2, x0, y0);
+        builder.close();
+    };
+
+    let coords = [0.0, 0.0, 100.0, 0.0, 100.0, 100.0, 50.0, 150.0];
+    for bad in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        for i in 0..coords.len() {
+            let mut coords = coords;
+            coords[i] = bad;
+            assert!(
+                Glyph::from_outline(1000, |builder| outline(builder, coords)).is_none()
+            );
+        }
+    }
+
+    let glyph = Glyph::from_outline(1000, |builder| outline(builder, coords)).unwrap();
+    for (x, y, size) in
+        [(f32::NAN, 0.0, 12.0), (0.0, f32::INFINITY, 12.0), (0.0, 0.0, f32::NAN)]
+    {
+        let bitmap = glyph.rasterize(x, y, size);
+        assert_eq!((bitmap.width, bitmap.height), (0, 0));
+        assert!(bitmap.coverage.is_empty());
+    }
+
+    // A huge outline with few units per em would need an enormous bitmap.
+    let huge = Glyph::from_outline(16, |builder| {
+        builder.move_to(-32767.0, -32767.0);
+        builder.line_to(32767.0, -32767.0);
+        builder.line_to(32767.0, 32767.0);
+        builder.line_to(-32767.0, 32767.0);
+        builder.close();
+    })
+    .unwrap();
+    for size in [12.0, 100.0] {
+        let bitmap = huge.rasterize(0.0, 0.0, size);
+        assert_eq!((bitmap.width, bitmap.height), (0, 0));
+        assert!(bitmap.coverage.is_empty());
+    }
+
+    // A flat outline would need an enormously wide bitmap without any
+    // height, and flips its bounding box for negative sizes.
+    let flat = Glyph::from_outline(1, |builder| {
+        builder.move_to(0.0, 0.0);
+        builder.line_to(30000.0, 0.0);
+        builder.close();
+    })
+    .unwrap();
+    for size in [1e6, -100.0] {
+        let bitmap = flat.rasterize(0.0, 0.0, size);
+        assert_eq!((bitmap.width, bitmap.height), (0, 0));
+        assert!(bitmap.coverage.is_empty());
+
+        // Streaming the rows must not allocate a huge row either.
+        let mut rows = flat.rasterize_rows(0.0, 0.0, size);
+        assert_eq!((rows.width(), rows.height()), (0, 0));
+        assert!(rows.next_row().is_none());
+    }
+}
+
+#[test]
+fn test_coverage_at() {
+    let glyph = glyph(SOURCE_SANS, 'g');
+    let (x, y, size) = (12.3, 45.6, 30.0);
+    let bitmap = glyph.rasterize(x, y, size);
+    for py in bitmap.top - 1..=bitmap.top + bitmap.height as i32 {
+        for px in bitmap.left - 1..=bitmap.left + bitmap.width as i32 {
+            let coverage = glyph.coverage_at(x, y, size, px, py);
+            assert!(
+                coverage.abs_diff(pixel(&bitmap, px, py)) <= 1,
+                "pixel ({px}, {py}) differs"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dilate_erode() {
+    let glyph = rect(100.0, 0.0, 400.0, 700.0);
+    let original = glyph.rasterize(3.3, 20.6, 20.0);
+    let mut bitmap = glyph.rasterize(3.3, 20.6, 20.0);
+    bitmap.dilate(2.0);
+    assert_eq!((bitmap.left, bitmap.top), (original.left - 2, original.top - 2));
+    assert_eq!((bitmap.width, bitmap.height), (original.width + 4, original.height + 4));
+    assert!(bitmap.diff(&original).differing > 0);
+
+    bitmap.erode(2.0);
+    assert!(bitmap.diff(&original).max_delta <= 1);
+}
+
+#[test]
+fn test_dilate_erode_fractional() {
+    // The rectangle spans exactly from (5, 6) to (11, 20) in pixels.
+    let glyph = rect(100.0, 0.0, 400.0, 700.0);
+    let row = |bitmap: &Bitmap| (2..14).map(|x| pixel(bitmap, x, 13)).collect::<Vec<_>>();
+
+    // Half of the second pixel next to each edge is covered.
+    let mut dilated = glyph.rasterize(3.0, 20.0, 20.0);
+    dilated.dilate(1.5);
+    assert_eq!((dilated.left, dilated.top), (2, 4));
+    assert_eq!(row(&dilated), [0, 128, 255, 255, 255, 255, 255, 255, 255, 255, 128, 0]);
+
+    // And half of the second pixel inside of each edge is uncovered.
+    let mut eroded = glyph.rasterize(3.0, 20.0, 20.0);
+    eroded.erode(1.5);
+    assert_eq!(row(&eroded), [0, 0, 0, 0, 127, 255, 255, 127, 0, 0, 0, 0]);
+
+    // Huge radii don't overflow.
+    eroded.dilate(1e20);
+    eroded.erode(1e20);
+    assert!(eroded.coverage.iter().all(|&c| c == 0));
+}
+
+#[test]
+fn test_rasterize_snapped() {
+    // A vertical stem that spans exactly ten pixels at a size of 100px.
+    let stem = rect(100.0, 0.0, 200.0, 700.0);
+
+    // Count the partially covered pixels in the middle row.
+    let partial = |bitmap: &Bitmap| {
+        let row = bitmap.height as usize / 2 * bitmap.width as usize;
+        bitmap.coverage[row..row + bitmap.width as usize]
+            .iter()
+            .filter(|&&c| c > 0 && c < 255)
+            .count()
+    };
+
+    let unsnapped = stem.rasterize(3.4, 80.3, 100.0);
+    let snapped = stem.rasterize_snapped(3.4, 80.3, 100.0);
+    assert_eq!(partial(&unsnapped), 2);
+    assert_eq!(partial(&snapped), 0);
+    assert_eq!(snapped.left, stem.rasterize(3.0, 80.0, 100.0).left);
+}
+
+#[test]
+fn test_rasterize_rows() {
+    let glyph = glyph(LIBERTINUS, 'g');
+    let bitmap = glyph.rasterize(4.7, 60.2, 50.0);
+    let mut rows = glyph.rasterize_rows(4.7, 60.2, 50.0);
+    assert_eq!((rows.left(), rows.top()), (bitmap.left, bitmap.top));
+    assert_eq!((rows.width(), rows.height()), (bitmap.width, bitmap.height));
+
+    let mut count = 0;
+    let chunks = bitmap.coverage.chunks_exact(bitmap.width as usize);
+    for (expected, chunk) in chunks.enumerate() {
+        let (index, row) = rows.next_row().unwrap();
+        assert_eq!(index as usize, expected);
+        assert!(row.iter().zip(chunk).all(|(a, b)| a.abs_diff(*b) <= 1));
+        count += 1;
+    }
+
+    assert_eq!(count, bitmap.height);
+    assert!(rows.next_row().is_none());
+}
+
 fn raster_letter(font: &[u8], letter: char, x: f32, y: f32, s: f32) -> bool {
     let out_path = format!("target/{}.ppm", letter);
     let ref_path = format!("tests/{}.ppm", letter);
 
-    let face = Face::parse(font, 0).unwrap();
-    let id = face.glyph_index(letter).unwrap();
-    let glyph = Glyph::load(&face, id).unwrap();
-    let bitmap = glyph.rasterize(x, y, s);
+    let bitmap = glyph(font, letter).rasterize(x, y, s);
 
     let mut ppm = vec![];
     write!(ppm, "P6\n{} {}\n255\n", bitmap.width, bitmap.height).unwrap();
@@ -46,12 +357,73 @@ fn raster_letter(font: &[u8], letter: char, x: f32, y: f32, s: f32) -> bool {
 
     std::fs::write(out_path, &ppm).unwrap();
 
-    let reference = std::fs::read(ref_path).ok();
+    let Some(reference) = std::fs::read(ref_path).ok().and_then(|data| parse_ppm(&data))
+    else {
+        eprintln!("Letter {letter:?} has no reference ❌");
+        return false;
+    };
+
+    if (reference.width, reference.height) != (bitmap.width, bitmap.height) {
+        eprintln!("Letter {letter:?} has different dimensions ❌");
+        return false;
+    }
 
-    let ok = Some(ppm) == reference;
+    let reference = Bitmap { left: bitmap.left, top: bitmap.top, ..reference };
+    let diff = bitmap.diff(&reference);
+    let ok = diff.max_delta <= TOLERANCE;
     if !ok {
-        eprintln!("Letter {letter:?} differs ❌");
+        eprintln!("Letter {letter:?} differs ❌ ({diff:?})");
     }
 
     ok
 }
+
+fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Glyph {
+    Glyph::from_outline(1000, |builder| {
+        builder.move_to(x0, y0);
+        builder.line_to(x1, y0);
+        builder.line_to(x1, y1);
+        builder.line_to(x0, y1);
+        builder.close();
+    })
+    .unwrap()
+}
+
+fn pixel(bitmap: &Bitmap, x: i32, y: i32) -> u8 {
+    let (column, row) = (x - bitmap.left, y - bitmap.top);
+    if column < 0
+        || column >= bitmap.width as i32
+        || row < 0
+        || row >= bitmap.height as i32
+    {
+        return 0;
+    }
+    bitmap.coverage[(row * bitmap.width as i32 + column) as usize]
+}
+
+fn glyph(font: &[u8], letter: char) -> Glyph {
+    let face = Face::parse(font, 0).unwrap();
+    let id = face.glyph_index(letter).unwrap();
+    Glyph::load(&face, id).unwrap()
+}
+
+fn parse_ppm(data: &[u8]) -> Option<Bitmap> {
+    // The header consists of three newline-terminated lines.
+    let mut newlines = data.iter().enumerate().filter(|(_, &b)| b == b'\n');
+    let end = newlines.nth(2)?.0 + 1;
+    let header = std::str::from_utf8(&data[..end]).ok()?;
+
+    let mut parts = header.split_ascii_whitespace();
+    if parts.next()? != "P6" {
+        return None;
+    }
+
+    let width: u32 = parts.next()?.parse().ok()?;
+    let height: u32 = parts.next()?.parse().ok()?;
+    let coverage: Vec<u8> = data[end..].chunks_exact(3).map(|px| 255 - px[0]).collect();
+    if coverage.len() != (width * height) as usize {
+        return None;
+    }
+
+    Some(Bitmap { left: 0, top: 0, width, height, coverage })
+}